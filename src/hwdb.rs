@@ -1,46 +1,47 @@
 use std::io::{self, Read};
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::{env, fs, mem};
 use std::collections::linked_list::Iter;
 
-use crate::{Error, Result, Udev, UdevEntry, UdevList};
+use memmap2::{Mmap, MmapOptions};
+
+use crate::{Error, Result, Udev, UdevDevice, UdevEntry, UdevList};
 
 mod line;
+mod resolver;
 mod trie;
 
 pub use line::*;
 pub use trie::*;
 
-static NODE_SIZE: AtomicUsize = AtomicUsize::new(24);
-static CHILD_ENTRY_SIZE: AtomicUsize = AtomicUsize::new(16);
-static VALUE_ENTRY_SIZE: AtomicUsize = AtomicUsize::new(32);
+pub(crate) use resolver::PropertyResolver;
 
-/// Gets the [Node](TrieNode) size loaded from the [TrieHeader].
-pub fn node_size() -> usize {
-    NODE_SIZE.load(Ordering::Relaxed)
-}
+// `node_size`/`child_entry_size`/`value_entry_size` used to be process-wide `AtomicUsize`
+// statics set from whichever `UdevHwdb` was constructed last. Opening two databases with
+// different format versions (e.g. a system `hwdb.bin` plus one built by `UdevHwdb::compile`)
+// corrupted parsing for both, since `TrieEntry::try_from` and `parse_nodes` read these globals regardless of
+// which database it was actually parsing. Record sizes now live on `TrieLayout`, threaded
+// through per-call from each `UdevHwdb`'s own `TrieHeader`; these free functions remain only as
+// thin wrappers over the default (v2) layout for source compatibility.
 
-pub(crate) fn set_node_size(val: usize) {
-    NODE_SIZE.store(val, Ordering::SeqCst);
+/// Gets the [Node](TrieNode) size this crate's own [TrieWriter] produces.
+pub fn node_size() -> usize {
+    TrieLayout::default().node_size()
 }
 
-/// Gets the [ChildEntry](TrieChildEntry) size loaded from the [TrieHeader].
+/// Gets the [ChildEntry](TrieChildEntry) size this crate's own [TrieWriter] produces.
 pub fn child_entry_size() -> usize {
-    CHILD_ENTRY_SIZE.load(Ordering::Relaxed)
-}
-
-pub(crate) fn set_child_entry_size(val: usize) {
-    CHILD_ENTRY_SIZE.store(val, Ordering::SeqCst);
+    TrieLayout::default().child_entry_size()
 }
 
-/// Gets the [ValueEntry](TrieValueEntry) size loaded from the [TrieHeader].
+/// Gets the [ValueEntry](TrieValueEntry) size this crate's own [TrieWriter] produces.
 pub fn value_entry_size() -> usize {
-    VALUE_ENTRY_SIZE.load(Ordering::Relaxed)
+    TrieLayout::default().value_entry_size()
 }
 
-pub(crate) fn set_value_entry_size(val: usize) {
-    VALUE_ENTRY_SIZE.store(val, Ordering::SeqCst);
+/// Strips a leading `0x`/`0X` from a sysfs hex attribute value (e.g. PCI `vendor`/`device`).
+fn strip_hex_prefix(s: &str) -> String {
+    s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s).to_owned()
 }
 
 #[cfg(target_os = "linux")]
@@ -61,15 +62,32 @@ fn get_hwdb_bin_paths() -> String {
     }
 }
 
+/// Backing storage for the on-disk HWDB: a zero-copy memory mapping on platforms/filesystems
+/// that support it, or the whole file read into memory otherwise.
+enum HwdbBuf {
+    Mmap(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl HwdbBuf {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Mmap(mmap) => mmap.as_ref(),
+            Self::Owned(buf) => buf.as_slice(),
+        }
+    }
+}
+
 /// Represents the on-disk hardware database.
 ///
 /// Retrieves properties from the hardware database.
-#[repr(C)]
 pub struct UdevHwdb {
     udev: Arc<Udev>,
     bin_paths: String,
     hwdb_path: String,
     head: TrieHeader,
+    layout: TrieLayout,
+    buf: HwdbBuf,
     properties_list: UdevList,
 }
 
@@ -79,58 +97,42 @@ impl UdevHwdb {
         let mut hwdb_path = String::new();
         let bin_paths = get_hwdb_bin_paths();
 
-        let (head, metadata) = {
-            // In the original `libudev`, they `mmap` the entire on-disk database into a `const char *`
-            // union, which leads to inherently unsafe access in Rust.
-            //
-            // Instead, we'll just parse the header for now, which advances the `File` struct's internal
-            // cursor, and delay further parsing for subsequent calls to the various node entry, and value calls.
-            //
-            // Alternatively, we could parse the properties list now, and avoid keeping the file
-            // struct, file metadata, and `TrieHeader` in the `UdevHwdb` struct. Instead, we would just
-            // keep the parsed `properties_list`.
-            //
-            // TBD.
-
-            let mut bin_file: Option<fs::File> = None;
-
-            for path in bin_paths.split('\0') {
-                if let Ok(f) = fs::OpenOptions::new().read(true).open(path) {
-                    bin_file = Some(f);
-                    path.clone_into(&mut hwdb_path);
-                    break;
-                }
-                let errno = io::Error::last_os_error();
-                if errno.raw_os_error() == Some(libc::ENOENT) {
-                    Ok(())
-                } else {
-                    Err(Error::UdevHwdb(format!(
-                        "error reading {path}, errno: {errno}"
-                    )))
-                }?;
+        let mut bin_file: Option<fs::File> = None;
+
+        for path in bin_paths.split('\0') {
+            if let Ok(f) = fs::OpenOptions::new().read(true).open(path) {
+                bin_file = Some(f);
+                path.clone_into(&mut hwdb_path);
+                break;
             }
+            let errno = io::Error::last_os_error();
+            if errno.raw_os_error() == Some(libc::ENOENT) {
+                Ok(())
+            } else {
+                Err(Error::UdevHwdb(format!(
+                    "error reading {path}, errno: {errno}"
+                )))
+            }?;
+        }
 
-            let mut file = bin_file.ok_or(Error::UdevHwdb(
-                "unable to find hwdb.bin database file".into(),
-            ))?;
+        let file = bin_file.ok_or(Error::UdevHwdb(
+            "unable to find hwdb.bin database file".into(),
+        ))?;
 
-            let metadata = file.metadata()?;
-            let mut hwdb_head_buf = [0u8; mem::size_of::<TrieHeader>()];
+        let buf = Self::map_file(&file)?;
 
-            file.read_exact(&mut hwdb_head_buf)?;
+        if buf.as_slice().len() < mem::size_of::<TrieHeader>() {
+            return Err(Error::UdevHwdb("HWDB header truncated".into()));
+        }
 
-            (TrieHeader::try_from(hwdb_head_buf.as_ref())?, metadata)
-        };
+        let head = TrieHeader::try_from(&buf.as_slice()[..mem::size_of::<TrieHeader>()])?;
+        let layout = TrieLayout::from_header(&head);
 
         let properties_list = UdevList::new(Arc::clone(&udev));
 
-        set_node_size(head.node_size() as usize);
-        set_child_entry_size(head.child_entry_size() as usize);
-        set_value_entry_size(head.value_entry_size() as usize);
-
         log::debug!("=== trie on-disk ===");
         log::debug!("tool version:           {}", head.tool_version());
-        log::debug!("file size:         {:8} bytes", metadata.len());
+        log::debug!("file size:         {:8} bytes", buf.as_slice().len());
         log::debug!("header size:       {:8} bytes", head.header_size());
         log::debug!("node size:         {:8} bytes", head.node_size());
         log::debug!("child size:        {:8} bytes", head.child_entry_size());
@@ -143,6 +145,8 @@ impl UdevHwdb {
             bin_paths,
             hwdb_path,
             head,
+            layout,
+            buf,
             properties_list,
         })
     }
@@ -152,6 +156,22 @@ impl UdevHwdb {
         &self.head
     }
 
+    /// Compiles one or more `.hwdb` text sources into an on-disk database image.
+    ///
+    /// Mirrors `systemd-hwdb update`: sources are given as `(filename, text)` pairs and merged
+    /// in the order given (lowest priority first) into a single trie, serialized into the exact
+    /// format [`UdevHwdb::new`] reads back. Callers typically write the result to `hwdb.bin` and
+    /// point `UDEV_HWDB_BIN` at it.
+    pub fn compile<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(sources: I) -> Result<Vec<u8>> {
+        let mut writer = TrieWriter::new();
+
+        for (filename, text) in sources {
+            writer.add_source(filename, text)?;
+        }
+
+        Ok(writer.compile())
+    }
+
     /// Looks up a matching device in the hardware database and populates property list.
     ///
     /// Parameters:
@@ -169,55 +189,70 @@ impl UdevHwdb {
     ///
     /// Returns: an iterator of [UdevEntry]s for device.
     pub fn get_properties_list_entry(&mut self, modalias: &str, _flags: u32) -> Result<Iter<UdevEntry>> {
-        // For now, do the naive thing, and read the entire HWDB into memory (12M+!!!)
-        //
-        // Using the BufReader to jump around to all the various offsets will probably be
-        // more efficient, but harder to follow. BufReader only supports relative `Seek`ing.
-        //
-        // Nodes are also not sequential in the on-disk format, which would make parsing
-        // easier, but lose some of the structure of the HWDB. According to the man page
-        // (`man 7 hwdb`), entries later in the HWDB have higher priority, which some tools
-        // may rely on.
-        //
-        // `libudev` does not appear to track priority.
-        //
-        // Loading everything into memory at one time also avoids some other tool updating the
-        // HWDB while we are parsing it.
-        let file = fs::OpenOptions::new()
-            .read(true)
-            .open(&self.hwdb_path).map_err(|err| {
-                log::warn!("unable to open HWDB file: {err}");
-                Error::UdevHwdb("unable to open HWDB file".into())
-            })?;
+        self.properties_list.clear();
 
-        let metadata = file
-            .metadata()
+        Self::trie_search(
+            &mut self.properties_list,
+            &self.head,
+            self.layout,
+            self.buf.as_slice(),
+            modalias,
+        )
             .map_err(|err| {
-                log::error!("unable to get HWDB metadata: {err}");
-                Error::UdevHwdb("unable to get HWDB metadata".into())
+                log::warn!("error looking up property list UdevEntry: {err}");
+                Error::UdevHwdb("error looking up property list UdevEntry".into())
             })?;
 
-        let file_len = metadata.len() as usize;
-
-        let mut reader = io::BufReader::new(file);
-        let mut hwdb_buf = Vec::with_capacity(file_len);
+        Ok(self.properties_list.iter())
+    }
 
-        reader
-            .read_to_end(&mut hwdb_buf)
+    /// Re-establishes the memory mapping over `hwdb_path`, picking up any changes an external
+    /// tool (e.g. `systemd-hwdb update`) made since this database was opened or last reloaded.
+    pub fn reload(&mut self) -> Result<()> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .open(&self.hwdb_path)
             .map_err(|err| {
-                log::warn!("error reading HWDB into memory: {err}");
-                Error::UdevHwdb("error reading HWDB into memory".into())
+                log::warn!("unable to open HWDB file: {err}");
+                Error::UdevHwdb("unable to open HWDB file".into())
             })?;
 
-        self.properties_list.clear();
+        let buf = Self::map_file(&file)?;
 
-        Self::trie_search(&mut self.properties_list, &self.head, &hwdb_buf, modalias)
-            .map_err(|err| {
-                log::warn!("error looking up property list UdevEntry: {err}");
-                Error::UdevHwdb("error looking up property list UdevEntry".into())
-            })?;
+        if buf.as_slice().len() < mem::size_of::<TrieHeader>() {
+            return Err(Error::UdevHwdb("HWDB header truncated".into()));
+        }
 
-        Ok(self.properties_list.iter())
+        self.head = TrieHeader::try_from(&buf.as_slice()[..mem::size_of::<TrieHeader>()])?;
+        self.layout = TrieLayout::from_header(&self.head);
+        self.buf = buf;
+
+        Ok(())
+    }
+
+    /// Maps `file` privately into memory, so lookups borrow straight from the mapping with no
+    /// per-query allocation or copy. The mapping reflects the file's contents as of this call;
+    /// an external tool replacing the file afterwards requires [`UdevHwdb::reload`] to observe
+    /// the change. Falls back to reading the whole file into memory if `mmap` is unavailable
+    /// (e.g. on filesystems that don't support it).
+    fn map_file(file: &fs::File) -> Result<HwdbBuf> {
+        // SAFETY: this is a private (copy-on-write), read-only mapping used only to read trie
+        // bytes through `&[u8]` slices for the lifetime of `HwdbBuf`; we never write through it.
+        match unsafe { MmapOptions::new().map_copy_read_only(file) } {
+            Ok(mmap) => Ok(HwdbBuf::Mmap(mmap)),
+            Err(err) => {
+                log::warn!("mmap of HWDB file failed ({err}), falling back to reading it into memory");
+
+                let mut buf = Vec::new();
+                let mut reader = file;
+                reader.read_to_end(&mut buf).map_err(|err| {
+                    log::warn!("error reading HWDB into memory: {err}");
+                    Error::UdevHwdb("error reading HWDB into memory".into())
+                })?;
+
+                Ok(HwdbBuf::Owned(buf))
+            }
+        }
     }
 
     /// Gets a reference to the [properties list](UdevList).
@@ -225,6 +260,75 @@ impl UdevHwdb {
         &self.properties_list
     }
 
+    /// Looks up `device` in the hardware database and merges the matching properties onto it.
+    ///
+    /// This mirrors the `hwdb` udev builtin: the modalias lookup key is taken directly from the
+    /// device's own `MODALIAS` property when present, otherwise one is synthesized from
+    /// bus-specific sysfs attributes (`usb:vVVVVpPPPP…`, `pci:vVVVVVVVVdDDDDDDDD…`,
+    /// `input:bBBBBvVVVVpPPPPeVVVV…`). Every resulting property (e.g. `ID_VENDOR_FROM_DATABASE`)
+    /// is added onto `device`, the same way `udevd` applies the database during device
+    /// processing.
+    pub fn query_device(&mut self, device: &mut UdevDevice) -> Result<()> {
+        for modalias in Self::device_modalias_keys(device) {
+            let entries: Vec<(String, String)> = self
+                .get_properties_list_entry(&modalias, 0)?
+                .map(|entry| (entry.name().to_owned(), entry.value().to_owned()))
+                .collect();
+
+            for (key, value) in entries {
+                device.set_property(&key, &value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Derives every modalias-style lookup key worth trying for `device`, in priority order.
+    fn device_modalias_keys(device: &UdevDevice) -> Vec<String> {
+        if let Some(modalias) = device.property_value("MODALIAS") {
+            return vec![modalias.to_owned()];
+        }
+
+        match device.subsystem() {
+            Some("usb") => {
+                let vendor = device.sysattr_value("idVendor");
+                let product = device.sysattr_value("idProduct");
+
+                match (vendor, product) {
+                    (Some(v), Some(p)) => vec![format!("usb:v{}p{}*", v.to_uppercase(), p.to_uppercase())],
+                    _ => Vec::new(),
+                }
+            }
+            Some("pci") => {
+                let vendor = device.sysattr_value("vendor").map(strip_hex_prefix);
+                let device_id = device.sysattr_value("device").map(strip_hex_prefix);
+
+                match (vendor, device_id) {
+                    (Some(v), Some(d)) => vec![format!("pci:v{:0>8}d{:0>8}*", v.to_uppercase(), d.to_uppercase())],
+                    _ => Vec::new(),
+                }
+            }
+            Some("input") => {
+                let bustype = device.sysattr_value("id/bustype").map(strip_hex_prefix);
+                let vendor = device.sysattr_value("id/vendor").map(strip_hex_prefix);
+                let product = device.sysattr_value("id/product").map(strip_hex_prefix);
+                let version = device.sysattr_value("id/version").map(strip_hex_prefix);
+
+                match (bustype, vendor, product, version) {
+                    (Some(b), Some(v), Some(p), Some(ver)) => vec![format!(
+                        "input:b{:0>4}v{:0>4}p{:0>4}e{:0>4}*",
+                        b.to_uppercase(),
+                        v.to_uppercase(),
+                        p.to_uppercase(),
+                        ver.to_uppercase()
+                    )],
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
     /// Adds a key-value pair to the property list.
     pub fn add_property(&mut self, key: &str, value: &str) -> Result<()> {
         Self::_add_property(&mut self.properties_list, key, value)
@@ -243,10 +347,14 @@ impl UdevHwdb {
     }
 
     /// Parses all [TrieEntry] nodes from an in-memory HWDB buffer.
+    ///
+    /// Record sizes are derived from `head` itself rather than a process-wide default, so this
+    /// works for any database regardless of its hwdb format version.
     pub fn parse_nodes<'a>(
         head: &'a TrieHeader,
         hwdb_buf: &'a [u8],
     ) -> impl Iterator<Item = TrieEntry> + 'a {
+        let layout = TrieLayout::from_header(head);
         let nodes_len = head.nodes_len() as usize;
         let node_start = mem::size_of::<TrieHeader>();
         let node_end = node_start.saturating_add(nodes_len);
@@ -260,9 +368,9 @@ impl UdevHwdb {
                 && (0..buf_len).contains(&node_end)
                 && idx < nodes_len
             {
-                TrieEntry::try_from(&hwdb_buf[idx..])
+                TrieEntry::from_bytes(&hwdb_buf[idx..], layout)
                     .map(|entry| {
-                        idx = idx.saturating_add(entry.len());
+                        idx = idx.saturating_add(entry.len(layout));
                         entry
                     })
                     .map_err(|err| {
@@ -278,15 +386,19 @@ impl UdevHwdb {
     fn trie_search(
         list: &mut UdevList,
         head: &TrieHeader,
+        layout: TrieLayout,
         hwdb_buf: &[u8],
         search: &str,
     ) -> Result<()> {
         let mut line_buf = LineBuf::new();
         let mut i = 0usize;
         let nodes_root_off = head.nodes_root_off() as usize;
+        // Collects every match across both the direct trie descent below and any `fnmatch`-ed
+        // subtrees, resolving same-key conflicts before anything reaches `list`.
+        let mut resolver = PropertyResolver::new();
 
         let mut node = if nodes_root_off < hwdb_buf.len() {
-            TrieEntry::try_from(&hwdb_buf[nodes_root_off..]).ok()
+            TrieEntry::from_bytes(&hwdb_buf[nodes_root_off..], layout).ok()
         } else {
             None
         };
@@ -300,34 +412,35 @@ impl UdevHwdb {
                 let ts = trie_string(hwdb_buf, prefix_off);
                 for (p, c) in ts.chars().enumerate() {
                     if c == '*' || c == '?' || c == '[' {
-                        return line_buf.trie_fnmatch(list, hwdb_buf, &n, p, &search[i + p..]);
+                        line_buf.trie_fnmatch(&mut resolver, hwdb_buf, layout, &n, p, &search[i + p..])?;
+                        return resolver.finish(list);
                     }
                     if search_count > i && Some(c) != search.chars().nth(i + p) {
-                        return Ok(());
+                        return resolver.finish(list);
                     }
                 }
 
                 i = i.saturating_add(ts.chars().count());
             }
 
-            if let Some(child) = n.lookup_child(hwdb_buf, b'*') {
+            if let Some(child) = n.lookup_child(hwdb_buf, b'*', layout) {
                 log::trace!("found matching child entry (glob): {child:?}");
                 line_buf.add_char(b'*')?;
-                line_buf.trie_fnmatch(list, hwdb_buf, &child, 0, &search[i..])?;
+                line_buf.trie_fnmatch(&mut resolver, hwdb_buf, layout, &child, 0, &search[i..])?;
                 line_buf.remove_char();
             }
 
-            if let Some(child) = n.lookup_child(hwdb_buf, b'?') {
+            if let Some(child) = n.lookup_child(hwdb_buf, b'?', layout) {
                 log::trace!("found matching child entry (optional): {child:?}");
                 line_buf.add_char(b'?')?;
-                line_buf.trie_fnmatch(list, hwdb_buf, &child, 0, &search[i..])?;
+                line_buf.trie_fnmatch(&mut resolver, hwdb_buf, layout, &child, 0, &search[i..])?;
                 line_buf.remove_char();
             }
 
-            if let Some(child) = n.lookup_child(hwdb_buf, b'[') {
+            if let Some(child) = n.lookup_child(hwdb_buf, b'[', layout) {
                 log::trace!("found matching child entry (range): {child:?}");
                 line_buf.add_char(b'[')?;
-                line_buf.trie_fnmatch(list, hwdb_buf, &child, 0, &search[i..])?;
+                line_buf.trie_fnmatch(&mut resolver, hwdb_buf, layout, &child, 0, &search[i..])?;
                 line_buf.remove_char();
             }
 
@@ -338,15 +451,15 @@ impl UdevHwdb {
 
                     log::trace!("Matching property, key: {key_str}, value: {val_str}");
 
-                    Self::_add_property(list, key_str, val_str)?;
+                    resolver.consider(key_str, val_str, value.file_priority(), value.line_number());
                 }
             }
 
-            node = n.lookup_child(hwdb_buf, *search.as_bytes().get(i).unwrap_or(&0));
+            node = n.lookup_child(hwdb_buf, *search.as_bytes().get(i).unwrap_or(&0), layout);
             i = i.saturating_add(1);
             log::trace!("No match found, searching next child[{i}]: {node:?}");
         }
 
-        Ok(())
+        resolver.finish(list)
     }
 }
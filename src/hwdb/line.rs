@@ -0,0 +1,170 @@
+use crate::{Error, Result};
+
+use super::{trie_string, PropertyResolver, TrieEntry, TrieLayout};
+
+const LINE_MAX: usize = 2048;
+
+/// Holds the glob branching byte (`*`, `?` or `[`) of the child node being glob-matched;
+/// [LineBuf::trie_fnmatch] puts it in front of that node's prefix to rebuild the full pattern.
+#[derive(Debug, Default)]
+pub struct LineBuf {
+    bytes: Vec<u8>,
+}
+
+impl LineBuf {
+    /// Creates an empty [LineBuf].
+    pub fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    /// Appends a byte to the line.
+    pub fn add_char(&mut self, c: u8) -> Result<()> {
+        if self.bytes.len() >= LINE_MAX {
+            return Err(Error::UdevHwdb("hwdb trace line too long".into()));
+        }
+        self.bytes.push(c);
+        Ok(())
+    }
+
+    /// Removes the last byte appended to the line.
+    pub fn remove_char(&mut self) {
+        self.bytes.pop();
+    }
+
+    /// Glob-matches `search` against every pattern reachable from `node`'s prefix (read starting
+    /// at character index `p`) down through its subtree, feeding every leaf whose full pattern
+    /// matches into `resolver` so same-key conflicts across matching leaves are resolved the same
+    /// way as a direct trie descent. Supports full POSIX `fnmatch` semantics: `*`, `?`, and
+    /// bracket expressions (`[a-f]`, `[abc]`, `[!...]`/`[^...]`, and a literal `]` as the first
+    /// member).
+    pub fn trie_fnmatch<'a>(
+        &mut self,
+        resolver: &mut PropertyResolver<'a>,
+        hwdb_buf: &'a [u8],
+        layout: TrieLayout,
+        node: &TrieEntry,
+        p: usize,
+        search: &str,
+    ) -> Result<()> {
+        let prefix = trie_string(hwdb_buf, node.node().prefix_off() as usize);
+        let mut tail = String::from_utf8_lossy(&self.bytes).into_owned();
+        tail.extend(prefix.chars().skip(p));
+
+        Self::walk(resolver, hwdb_buf, layout, node, &tail, search)
+    }
+
+    fn walk<'a>(
+        resolver: &mut PropertyResolver<'a>,
+        hwdb_buf: &'a [u8],
+        layout: TrieLayout,
+        node: &TrieEntry,
+        pattern: &str,
+        search: &str,
+    ) -> Result<()> {
+        if !node.values().is_empty() && fnmatch(pattern, search) {
+            for value in node.values() {
+                let key = trie_string(hwdb_buf, value.key_off() as usize);
+                let val = trie_string(hwdb_buf, value.value_off() as usize);
+                resolver.consider(key, val, value.file_priority(), value.line_number());
+            }
+        }
+
+        for child_entry in node.children() {
+            let child_off = child_entry.child_off() as usize;
+            if child_off >= hwdb_buf.len() {
+                continue;
+            }
+
+            if let Ok(child) = TrieEntry::from_bytes(&hwdb_buf[child_off..], layout) {
+                let child_prefix = trie_string(hwdb_buf, child.node().prefix_off() as usize);
+                let child_pattern = format!("{pattern}{}{child_prefix}", child_entry.c() as char);
+                Self::walk(resolver, hwdb_buf, layout, &child, &child_pattern, search)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Bracket-expression member: either a single character or an inclusive range.
+enum BracketItem {
+    Char(char),
+    Range(char, char),
+}
+
+/// Parses a `[...]` bracket expression starting at `pattern[start]` (which must be `'['`).
+///
+/// Returns the index just past the closing `]`, whether the set is negated (`[!...]`/`[^...]`),
+/// and the set's members. A `]` as the first member (optionally right after the negation marker)
+/// is treated as a literal character rather than closing the set, matching POSIX `fnmatch`.
+fn parse_bracket(pattern: &[char], start: usize) -> (usize, bool, Vec<BracketItem>) {
+    let mut i = start + 1;
+
+    let negate = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut items = Vec::new();
+    let mut first = true;
+
+    loop {
+        match pattern.get(i) {
+            None => break,
+            Some(']') if !first => {
+                i += 1;
+                break;
+            }
+            Some(&c) => {
+                first = false;
+                if pattern.get(i + 1) == Some(&'-') && matches!(pattern.get(i + 2), Some(&end) if end != ']') {
+                    items.push(BracketItem::Range(c, pattern[i + 2]));
+                    i += 3;
+                } else {
+                    items.push(BracketItem::Char(c));
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    (i, negate, items)
+}
+
+fn bracket_matches(items: &[BracketItem], negate: bool, c: char) -> bool {
+    let found = items.iter().any(|item| match item {
+        BracketItem::Char(m) => *m == c,
+        BracketItem::Range(lo, hi) => *lo <= c && c <= *hi,
+    });
+
+    found != negate
+}
+
+/// Matches `text` against a POSIX shell glob `pattern` (`*`, `?`, and `[...]` bracket expressions).
+fn fnmatch(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    fnmatch_at(&pattern, 0, &text, 0)
+}
+
+fn fnmatch_at(pattern: &[char], pi: usize, text: &[char], ti: usize) -> bool {
+    let Some(&c) = pattern.get(pi) else {
+        return ti == text.len();
+    };
+
+    match c {
+        // `*` backtracks over every possible split point, so later literals in the pattern can
+        // still match (e.g. `usb:v*p0001*` against `usb:v1D6Bp0001rev02`).
+        '*' => (ti..=text.len()).any(|k| fnmatch_at(pattern, pi + 1, text, k)),
+        '?' => ti < text.len() && fnmatch_at(pattern, pi + 1, text, ti + 1),
+        '[' => {
+            let (next_pi, negate, items) = parse_bracket(pattern, pi);
+            match text.get(ti) {
+                Some(&tc) if bracket_matches(&items, negate, tc) => fnmatch_at(pattern, next_pi, text, ti + 1),
+                _ => false,
+            }
+        }
+        lit => ti < text.len() && text[ti] == lit && fnmatch_at(pattern, pi + 1, text, ti + 1),
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use crate::{Result, UdevList};
+
+use super::UdevHwdb;
+
+/// Accumulates the winning `(file_priority, line_number)` value for each property key seen
+/// during a single [UdevHwdb::get_properties_list_entry] search, across every lookup path that
+/// search takes (direct trie descent as well as any `fnmatch`-matched subtrees). Property
+/// conflicts are resolved here, before anything reaches [UdevList], so a lower-priority match of
+/// a key can never be observed by a caller even if it was encountered first.
+#[derive(Default)]
+pub(crate) struct PropertyResolver<'a> {
+    winners: HashMap<&'a str, (u16, u32, &'a str)>,
+}
+
+impl<'a> PropertyResolver<'a> {
+    pub(crate) fn new() -> Self {
+        Self { winners: HashMap::new() }
+    }
+
+    /// Considers a `(key, value)` match found at `file_priority`/`line_number`, keeping it only
+    /// if it outranks whatever currently wins for `key` (a higher priority, or the same priority
+    /// at a later line).
+    pub(crate) fn consider(&mut self, key: &'a str, value: &'a str, file_priority: u16, line_number: u32) {
+        let wins = match self.winners.get(key) {
+            Some(&(cur_priority, cur_line, _)) => {
+                file_priority > cur_priority || (file_priority == cur_priority && line_number > cur_line)
+            }
+            None => true,
+        };
+
+        if wins {
+            self.winners.insert(key, (file_priority, line_number, value));
+        }
+    }
+
+    /// Adds every surviving winner onto `list`, consuming the resolver.
+    pub(crate) fn finish(self, list: &mut UdevList) -> Result<()> {
+        for (key, (_, _, value)) in self.winners {
+            UdevHwdb::_add_property(list, key, value)?;
+        }
+
+        Ok(())
+    }
+}
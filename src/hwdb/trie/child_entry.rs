@@ -0,0 +1,85 @@
+use std::cmp;
+use std::mem;
+
+use crate::{Error, Result};
+
+/// On-disk trie child entry (`struct trie_child_entry_f`), keyed by a single branching byte.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrieChildEntry {
+    c: u8,
+    child_off: u64,
+}
+
+impl TrieChildEntry {
+    /// Creates a new, empty [TrieChildEntry].
+    pub const fn new() -> Self {
+        Self { c: 0, child_off: 0 }
+    }
+
+    /// Gets the branching byte this entry is keyed on.
+    pub const fn c(&self) -> u8 {
+        self.c
+    }
+
+    /// Gets the offset of the child [TrieEntry](super::TrieEntry) in the on-disk buffer.
+    pub const fn child_off(&self) -> u64 {
+        self.child_off
+    }
+
+    /// Sets the branching byte this entry is keyed on.
+    pub fn with_c(mut self, c: u8) -> Self {
+        self.c = c;
+        self
+    }
+
+    pub(crate) fn with_child_off(mut self, off: u64) -> Self {
+        self.child_off = off;
+        self
+    }
+
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(mem::size_of::<Self>());
+        buf.push(self.c);
+        buf.resize(mem::size_of::<Self>() - mem::size_of::<u64>(), 0);
+        buf.extend_from_slice(&self.child_off.to_le_bytes());
+        buf
+    }
+}
+
+// Entries are only ever looked up and sorted by their branching byte `c`; the offset they carry
+// must not participate in equality/ordering or `lookup_child`'s search-by-`c` would break.
+impl PartialEq for TrieChildEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.c == other.c
+    }
+}
+
+impl Eq for TrieChildEntry {}
+
+impl PartialOrd for TrieChildEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TrieChildEntry {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.c.cmp(&other.c)
+    }
+}
+
+impl TryFrom<&[u8]> for TrieChildEntry {
+    type Error = Error;
+
+    fn try_from(val: &[u8]) -> Result<Self> {
+        if val.len() < mem::size_of::<Self>() {
+            return Err(Error::UdevHwdb("truncated trie child entry".into()));
+        }
+
+        let off_start = mem::size_of::<Self>() - mem::size_of::<u64>();
+        let child_off = u64::from_le_bytes(val[off_start..off_start + 8].try_into().unwrap());
+
+        Ok(Self::new().with_c(val[0]).with_child_off(child_off))
+    }
+}
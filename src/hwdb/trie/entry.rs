@@ -1,8 +1,8 @@
-use std::{cmp, mem};
+use std::cmp;
 
 use crate::{Error, Result};
 
-use super::{TrieChildEntry, TrieNode, TrieValueEntry};
+use super::{TrieChildEntry, TrieLayout, TrieNode, TrieValueEntry};
 
 /// Represents the full Trie entry in the HWDB.
 #[repr(C)]
@@ -38,18 +38,17 @@ impl TrieEntry {
         self.values.as_ref()
     }
 
-    /// Gets the total length of the [TrieEntry].
-    pub fn len(&self) -> usize {
-        let children_len = self
-            .children
-            .len()
-            .saturating_mul(mem::size_of::<TrieChildEntry>());
-        let values_len = self
-            .values
-            .len()
-            .saturating_mul(mem::size_of::<TrieValueEntry>());
-
-        mem::size_of::<TrieNode>()
+    /// Gets the total on-disk length of the [TrieEntry] under `layout`'s record sizes.
+    ///
+    /// Must be called with the same [TrieLayout] the entry was parsed with: value entries vary
+    /// in size between hwdb format v1 and v2, so this cannot be inferred from the parsed struct
+    /// alone.
+    pub fn len(&self, layout: TrieLayout) -> usize {
+        let children_len = self.children.len().saturating_mul(layout.child_entry_size());
+        let values_len = self.values.len().saturating_mul(layout.value_entry_size());
+
+        layout
+            .node_size()
             .saturating_add(children_len)
             .saturating_add(values_len)
     }
@@ -60,9 +59,10 @@ impl TrieEntry {
     ///
     /// - `hwdb_buf`: in-memory buffer of the entire HWDB.
     /// - `c`: Child index to search the list of [TrieChildEntry].
+    /// - `layout`: record sizes for the database `hwdb_buf` belongs to.
     ///
     /// Returns [Some(TrieNode)](TrieNode) on success, [`None`] otherwise.
-    pub fn lookup_child(&self, hwdb_buf: &[u8], c: u8) -> Option<Self> {
+    pub fn lookup_child(&self, hwdb_buf: &[u8], c: u8, layout: TrieLayout) -> Option<Self> {
         let search = TrieChildEntry::new().with_c(c);
         let buf_len = hwdb_buf.len();
 
@@ -75,46 +75,67 @@ impl TrieEntry {
 
         // if the child offset is in range, attempt to construct a `TrieNode` at that offset
         if (0..buf_len).contains(&child_off) {
-            Self::try_from(&hwdb_buf[child_off..]).ok()
+            Self::from_bytes(&hwdb_buf[child_off..], layout).ok()
         } else {
             None
         }
     }
-}
-
-impl TryFrom<&[u8]> for TrieEntry {
-    type Error = Error;
 
-    fn try_from(val: &[u8]) -> Result<Self> {
+    /// Parses a [TrieEntry] out of `val` using `layout`'s record sizes.
+    ///
+    /// Record sizes come from the originating database's own [TrieHeader] rather than a
+    /// process-wide default, so differently-versioned (or concurrently open) databases parse
+    /// independently of one another. [`TryFrom<&[u8]>`](TrieEntry) is kept as a convenience for
+    /// this crate's own default (v2) layout.
+    pub fn from_bytes(val: &[u8], layout: TrieLayout) -> Result<Self> {
         let node = TrieNode::try_from(val)?;
 
-        let mut idx = mem::size_of::<TrieNode>();
+        let mut idx = layout.node_size();
 
         let val_end = val.len();
-        let child_len = mem::size_of::<TrieChildEntry>();
+        let child_len = layout.child_entry_size();
         let child_count = node.children_count() as usize;
-        let child_end = idx.saturating_add(child_count.saturating_mul(child_len).saturating_sub(1));
 
         let mut children: Vec<TrieChildEntry> = Vec::with_capacity(child_count);
 
-        if (idx..val_end).contains(&child_end) && child_count > 0 {
-            for c in val[idx..].chunks_exact(child_len).take(child_count) {
-                children.push(c.try_into()?);
-                idx = idx.saturating_add(child_len);
+        if child_count > 0 {
+            // A corrupt or malicious header can report a zero record size; `chunks_exact` panics
+            // on a zero chunk size, so reject that instead of ever calling it.
+            if child_len == 0 {
+                return Err(Error::UdevHwdb("HWDB header reports a zero child entry size".into()));
+            }
+
+            let child_end = idx.saturating_add(child_count.saturating_mul(child_len).saturating_sub(1));
+
+            if (idx..val_end).contains(&child_end) {
+                for c in val[idx..].chunks_exact(child_len).take(child_count) {
+                    children.push(c.try_into()?);
+                    idx = idx.saturating_add(child_len);
+                }
             }
         }
 
         children.sort();
 
-        let value_len = mem::size_of::<TrieValueEntry>();
+        // Unlike `TrieChildEntry`, whose on-disk size never varies in practice, value entries
+        // are 16 bytes under hwdb format v1 and 32 bytes under v2 (see `TrieValueEntry`); honor
+        // whatever size this database's header reported rather than assuming the v2 layout.
+        let value_len = layout.value_entry_size();
         let value_count = node.values_count() as usize;
-        let value_end = idx.saturating_add(value_count.saturating_mul(value_len).saturating_sub(1));
 
         let mut values: Vec<TrieValueEntry> = Vec::with_capacity(value_count);
 
-        if (idx..val_end).contains(&value_end) && value_count > 0 {
-            for c in val[idx..].chunks_exact(value_len).take(value_count) {
-                values.push(c.try_into()?);
+        if value_count > 0 {
+            if value_len == 0 {
+                return Err(Error::UdevHwdb("HWDB header reports a zero value entry size".into()));
+            }
+
+            let value_end = idx.saturating_add(value_count.saturating_mul(value_len).saturating_sub(1));
+
+            if (idx..val_end).contains(&value_end) {
+                for c in val[idx..].chunks_exact(value_len).take(value_count) {
+                    values.push(c.try_into()?);
+                }
             }
         }
 
@@ -124,4 +145,12 @@ impl TryFrom<&[u8]> for TrieEntry {
             values,
         })
     }
+}
+
+impl TryFrom<&[u8]> for TrieEntry {
+    type Error = Error;
+
+    fn try_from(val: &[u8]) -> Result<Self> {
+        Self::from_bytes(val, TrieLayout::default())
+    }
 }
\ No newline at end of file
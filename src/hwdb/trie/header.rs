@@ -0,0 +1,147 @@
+use std::mem;
+
+use crate::{Error, Result};
+
+const HWDB_SIG: &[u8; 8] = b"KSLPHHRH";
+
+/// On-disk HWDB file header (`struct trie_header_f`).
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrieHeader {
+    signature: [u8; 8],
+    tool_version: u64,
+    file_size: u64,
+    header_size: u64,
+    node_size: u64,
+    child_entry_size: u64,
+    value_entry_size: u64,
+    strings_off: u64,
+    strings_len: u64,
+    nodes_root_off: u64,
+    nodes_len: u64,
+}
+
+impl TrieHeader {
+    /// Builds a fresh header describing a database about to be serialized by [`TrieWriter`](super::TrieWriter).
+    pub(crate) fn build(
+        node_size: u64,
+        child_entry_size: u64,
+        value_entry_size: u64,
+        strings_off: u64,
+        strings_len: u64,
+        nodes_root_off: u64,
+        nodes_len: u64,
+    ) -> Self {
+        let header_size = mem::size_of::<Self>() as u64;
+        // `nodes_root_off` is the root node's own byte offset, not the start of the nodes
+        // region: `flatten`'s post-order traversal pushes every child before its parent, so for
+        // any non-trivial tree the root sits near the *end* of the nodes region, not the start.
+        // The file's true end is the start of the nodes region (right after the strings pool)
+        // plus its total length.
+        let file_size = strings_off + strings_len + nodes_len;
+
+        Self {
+            signature: *HWDB_SIG,
+            tool_version: 1,
+            file_size,
+            header_size,
+            node_size,
+            child_entry_size,
+            value_entry_size,
+            strings_off,
+            strings_len,
+            nodes_root_off,
+            nodes_len,
+        }
+    }
+
+    /// Serializes the header into its on-disk byte layout.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(mem::size_of::<Self>());
+        buf.extend_from_slice(&self.signature);
+        buf.extend_from_slice(&self.tool_version.to_le_bytes());
+        buf.extend_from_slice(&self.file_size.to_le_bytes());
+        buf.extend_from_slice(&self.header_size.to_le_bytes());
+        buf.extend_from_slice(&self.node_size.to_le_bytes());
+        buf.extend_from_slice(&self.child_entry_size.to_le_bytes());
+        buf.extend_from_slice(&self.value_entry_size.to_le_bytes());
+        buf.extend_from_slice(&self.strings_off.to_le_bytes());
+        buf.extend_from_slice(&self.strings_len.to_le_bytes());
+        buf.extend_from_slice(&self.nodes_root_off.to_le_bytes());
+        buf.extend_from_slice(&self.nodes_len.to_le_bytes());
+        buf
+    }
+
+    pub const fn tool_version(&self) -> u64 {
+        self.tool_version
+    }
+
+    pub const fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    pub const fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    pub const fn node_size(&self) -> u64 {
+        self.node_size
+    }
+
+    pub const fn child_entry_size(&self) -> u64 {
+        self.child_entry_size
+    }
+
+    pub const fn value_entry_size(&self) -> u64 {
+        self.value_entry_size
+    }
+
+    pub const fn strings_off(&self) -> u64 {
+        self.strings_off
+    }
+
+    pub const fn strings_len(&self) -> u64 {
+        self.strings_len
+    }
+
+    pub const fn nodes_root_off(&self) -> u64 {
+        self.nodes_root_off
+    }
+
+    pub const fn nodes_len(&self) -> u64 {
+        self.nodes_len
+    }
+}
+
+impl TryFrom<&[u8]> for TrieHeader {
+    type Error = Error;
+
+    fn try_from(val: &[u8]) -> Result<Self> {
+        if val.len() < mem::size_of::<Self>() {
+            return Err(Error::UdevHwdb("HWDB header truncated".into()));
+        }
+
+        let mut signature = [0u8; 8];
+        signature.copy_from_slice(&val[0..8]);
+
+        if &signature != HWDB_SIG {
+            return Err(Error::UdevHwdb("invalid HWDB signature".into()));
+        }
+
+        let read_u64 = |off: usize| -> u64 { u64::from_le_bytes(val[off..off + 8].try_into().unwrap()) };
+
+        Ok(Self {
+            signature,
+            tool_version: read_u64(8),
+            file_size: read_u64(16),
+            header_size: read_u64(24),
+            node_size: read_u64(32),
+            child_entry_size: read_u64(40),
+            value_entry_size: read_u64(48),
+            strings_off: read_u64(56),
+            strings_len: read_u64(64),
+            nodes_root_off: read_u64(72),
+            nodes_len: read_u64(80),
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use std::mem;
+
+use super::{TrieChildEntry, TrieHeader, TrieNode};
+
+/// On-disk record sizes for a single HWDB file.
+///
+/// `TrieNode`, `TrieChildEntry`, and `TrieValueEntry` record sizes come from each database's own
+/// [TrieHeader] rather than a process-wide default, so two differently-versioned (or concurrent)
+/// databases parse independently instead of corrupting each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrieLayout {
+    node_size: usize,
+    child_entry_size: usize,
+    value_entry_size: usize,
+}
+
+impl TrieLayout {
+    /// Creates a new [TrieLayout] from explicit record sizes.
+    pub const fn new(node_size: usize, child_entry_size: usize, value_entry_size: usize) -> Self {
+        Self {
+            node_size,
+            child_entry_size,
+            value_entry_size,
+        }
+    }
+
+    /// Derives the layout a database's own [TrieHeader] describes.
+    pub fn from_header(head: &TrieHeader) -> Self {
+        Self::new(
+            head.node_size() as usize,
+            head.child_entry_size() as usize,
+            head.value_entry_size() as usize,
+        )
+    }
+
+    pub const fn node_size(&self) -> usize {
+        self.node_size
+    }
+
+    pub const fn child_entry_size(&self) -> usize {
+        self.child_entry_size
+    }
+
+    pub const fn value_entry_size(&self) -> usize {
+        self.value_entry_size
+    }
+}
+
+impl Default for TrieLayout {
+    /// The layout this crate's own [TrieWriter](super::TrieWriter) produces (hwdb format v2).
+    fn default() -> Self {
+        Self::new(mem::size_of::<TrieNode>(), mem::size_of::<TrieChildEntry>(), 32)
+    }
+}
@@ -0,0 +1,29 @@
+use std::ffi::CStr;
+
+mod child_entry;
+mod entry;
+mod header;
+mod layout;
+mod node;
+mod value_entry;
+mod writer;
+
+pub use child_entry::*;
+pub use entry::*;
+pub use header::*;
+pub use layout::*;
+pub use node::*;
+pub use value_entry::*;
+pub use writer::*;
+
+/// Reads a NUL-terminated string out of the HWDB strings pool at `off`.
+pub fn trie_string(hwdb_buf: &[u8], off: usize) -> &str {
+    if off == 0 || off >= hwdb_buf.len() {
+        return "";
+    }
+
+    CStr::from_bytes_until_nul(&hwdb_buf[off..])
+        .ok()
+        .and_then(|s| s.to_str().ok())
+        .unwrap_or("")
+}
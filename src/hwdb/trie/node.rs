@@ -0,0 +1,78 @@
+use std::mem;
+
+use crate::{Error, Result};
+
+/// On-disk trie node (`struct trie_node_f`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrieNode {
+    prefix_off: u64,
+    children_count: u64,
+    values_count: u64,
+}
+
+impl TrieNode {
+    /// Creates a new, empty [TrieNode].
+    pub const fn new() -> Self {
+        Self {
+            prefix_off: 0,
+            children_count: 0,
+            values_count: 0,
+        }
+    }
+
+    /// Gets the offset of this node's shared key prefix in the strings pool, or `0` if empty.
+    pub const fn prefix_off(&self) -> u64 {
+        self.prefix_off
+    }
+
+    /// Gets the number of [TrieChildEntry](super::TrieChildEntry) records following this node.
+    pub const fn children_count(&self) -> u64 {
+        self.children_count
+    }
+
+    /// Gets the number of [TrieValueEntry](super::TrieValueEntry) records following this node's children.
+    pub const fn values_count(&self) -> u64 {
+        self.values_count
+    }
+
+    pub(crate) fn with_prefix_off(mut self, off: u64) -> Self {
+        self.prefix_off = off;
+        self
+    }
+
+    pub(crate) fn with_children_count(mut self, count: u64) -> Self {
+        self.children_count = count;
+        self
+    }
+
+    pub(crate) fn with_values_count(mut self, count: u64) -> Self {
+        self.values_count = count;
+        self
+    }
+
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(mem::size_of::<Self>());
+        buf.extend_from_slice(&self.prefix_off.to_le_bytes());
+        buf.extend_from_slice(&self.children_count.to_le_bytes());
+        buf.extend_from_slice(&self.values_count.to_le_bytes());
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for TrieNode {
+    type Error = Error;
+
+    fn try_from(val: &[u8]) -> Result<Self> {
+        if val.len() < mem::size_of::<Self>() {
+            return Err(Error::UdevHwdb("truncated trie node".into()));
+        }
+
+        let read_u64 = |off: usize| -> u64 { u64::from_le_bytes(val[off..off + 8].try_into().unwrap()) };
+
+        Ok(Self::new()
+            .with_prefix_off(read_u64(0))
+            .with_children_count(read_u64(8))
+            .with_values_count(read_u64(16)))
+    }
+}
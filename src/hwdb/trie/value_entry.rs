@@ -0,0 +1,126 @@
+use std::mem;
+
+use crate::{Error, Result};
+
+use super::trie_string;
+
+/// On-disk trie value entry: a single resolved `key=value` property.
+///
+/// Format v1 is 16 bytes (`key_off`/`value_off` only). Format v2 is 32 bytes and additionally
+/// tracks the source `.hwdb` file, line number and priority a property came from, so that
+/// conflicting definitions across files can be resolved deterministically.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrieValueEntry {
+    key_off: u64,
+    value_off: u64,
+    filename_off: u64,
+    line_number: u32,
+    file_priority: u16,
+}
+
+impl TrieValueEntry {
+    /// Creates a new, empty [TrieValueEntry].
+    pub const fn new() -> Self {
+        Self {
+            key_off: 0,
+            value_off: 0,
+            filename_off: 0,
+            line_number: 0,
+            file_priority: 0,
+        }
+    }
+
+    /// Gets the offset of the property key in the strings pool.
+    pub const fn key_off(&self) -> u64 {
+        self.key_off
+    }
+
+    /// Gets the offset of the property value in the strings pool.
+    pub const fn value_off(&self) -> u64 {
+        self.value_off
+    }
+
+    /// Gets the offset of the source file name in the strings pool, or `0` for v1 entries.
+    pub const fn filename_off(&self) -> u64 {
+        self.filename_off
+    }
+
+    /// Gets the line number within the source file this property was defined on.
+    pub const fn line_number(&self) -> u32 {
+        self.line_number
+    }
+
+    /// Gets the file priority used to resolve conflicting definitions of the same key.
+    pub const fn file_priority(&self) -> u16 {
+        self.file_priority
+    }
+
+    /// Resolves the source `.hwdb` file name this property came from, if known.
+    pub fn filename<'a>(&self, hwdb_buf: &'a [u8]) -> Option<&'a str> {
+        (self.filename_off != 0).then(|| trie_string(hwdb_buf, self.filename_off as usize))
+    }
+
+    pub(crate) fn with_key_off(mut self, off: u64) -> Self {
+        self.key_off = off;
+        self
+    }
+
+    pub(crate) fn with_value_off(mut self, off: u64) -> Self {
+        self.value_off = off;
+        self
+    }
+
+    pub(crate) fn with_filename_off(mut self, off: u64) -> Self {
+        self.filename_off = off;
+        self
+    }
+
+    pub(crate) fn with_line_number(mut self, line: u32) -> Self {
+        self.line_number = line;
+        self
+    }
+
+    pub(crate) fn with_file_priority(mut self, priority: u16) -> Self {
+        self.file_priority = priority;
+        self
+    }
+
+    /// Serializes this entry into v2 (32-byte) on-disk form.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(mem::size_of::<Self>());
+        buf.extend_from_slice(&self.key_off.to_le_bytes());
+        buf.extend_from_slice(&self.value_off.to_le_bytes());
+        buf.extend_from_slice(&self.filename_off.to_le_bytes());
+        buf.extend_from_slice(&self.line_number.to_le_bytes());
+        buf.extend_from_slice(&self.file_priority.to_le_bytes());
+        buf.extend_from_slice(&[0u8; 2]); // padding
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for TrieValueEntry {
+    type Error = Error;
+
+    // Accepts either a 16-byte v1 slice (key/value only, priority implicitly `0`) or a 32-byte
+    // v2 slice carrying the extra filename/line/priority fields.
+    fn try_from(val: &[u8]) -> Result<Self> {
+        if val.len() < 16 {
+            return Err(Error::UdevHwdb("truncated trie value entry".into()));
+        }
+
+        let key_off = u64::from_le_bytes(val[0..8].try_into().unwrap());
+        let value_off = u64::from_le_bytes(val[8..16].try_into().unwrap());
+
+        let mut entry = Self::new().with_key_off(key_off).with_value_off(value_off);
+
+        if val.len() >= 32 {
+            entry = entry
+                .with_filename_off(u64::from_le_bytes(val[16..24].try_into().unwrap()))
+                .with_line_number(u32::from_le_bytes(val[24..28].try_into().unwrap()))
+                .with_file_priority(u16::from_le_bytes(val[28..30].try_into().unwrap()));
+        }
+
+        Ok(entry)
+    }
+}
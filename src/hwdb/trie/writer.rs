@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+
+use crate::{Error, Result};
+
+use super::{TrieChildEntry, TrieHeader, TrieNode, TrieValueEntry};
+
+/// Deduplicated, append-only pool of NUL-terminated strings backing every `*_off` field in the
+/// on-disk trie. Offset `0` is reserved to mean "absent" (e.g. a node with no shared prefix).
+struct StringPool {
+    buf: Vec<u8>,
+    offsets: HashMap<String, u64>,
+}
+
+impl StringPool {
+    fn new() -> Self {
+        Self {
+            buf: vec![0],
+            offsets: HashMap::new(),
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> u64 {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&off) = self.offsets.get(s) {
+            return off;
+        }
+
+        let off = self.buf.len() as u64;
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+        self.offsets.insert(s.to_owned(), off);
+        off
+    }
+}
+
+/// A property value pending insertion, tagged with the source location it was parsed from so
+/// conflicting definitions of the same key can later be resolved by priority.
+struct BuildValue {
+    key: String,
+    value: String,
+    filename: String,
+    line_number: u32,
+    file_priority: u16,
+}
+
+/// In-memory node used while building a trie, before it is flattened into the on-disk format.
+#[derive(Default)]
+struct BuildNode {
+    /// Prefix bytes shared by every key below this node (empty at the root).
+    prefix: Vec<u8>,
+    /// Property values that terminate exactly at this node.
+    values: Vec<BuildValue>,
+    /// Children keyed by their branching byte; kept sorted via `BTreeMap` so they already come
+    /// out in the order the on-disk `TrieChildEntry` array must be in.
+    children: BTreeMap<u8, BuildNode>,
+}
+
+impl BuildNode {
+    fn new(prefix: Vec<u8>) -> Self {
+        Self {
+            prefix,
+            ..Default::default()
+        }
+    }
+
+    /// Inserts `key` below `self`, splitting an existing child's prefix if `key` diverges
+    /// partway through it.
+    fn insert(&mut self, key: &[u8], value: BuildValue) {
+        let common = self
+            .prefix
+            .iter()
+            .zip(key.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        if common < self.prefix.len() {
+            let tail = self.prefix.split_off(common);
+            let mut split = BuildNode::new(tail[1..].to_vec());
+            split.values = mem::take(&mut self.values);
+            split.children = mem::take(&mut self.children);
+            self.children.insert(tail[0], split);
+
+            if common < key.len() {
+                let mut child = BuildNode::new(key[common + 1..].to_vec());
+                child.values.push(value);
+                self.children.insert(key[common], child);
+            } else {
+                self.values.push(value);
+            }
+            return;
+        }
+
+        let rest = &key[common..];
+        if rest.is_empty() {
+            self.values.push(value);
+            return;
+        }
+
+        self.children
+            .entry(rest[0])
+            .or_insert_with(|| BuildNode::new(rest[1..].to_vec()))
+            .insert(&rest[1..], value);
+    }
+}
+
+/// A value entry, flattened to string-pool offsets, ready to be serialized.
+struct FlatValue {
+    key_off: u64,
+    value_off: u64,
+    filename_off: u64,
+    line_number: u32,
+    file_priority: u16,
+}
+
+/// A flattened node, ready to be serialized once every node's final byte offset is known.
+struct FlatNode {
+    prefix_off: u64,
+    children: Vec<(u8, usize)>,
+    values: Vec<FlatValue>,
+    byte_offset: u64,
+}
+
+impl FlatNode {
+    fn byte_size(&self) -> u64 {
+        mem::size_of::<TrieNode>() as u64
+            + self.children.len() as u64 * mem::size_of::<TrieChildEntry>() as u64
+            + self.values.len() as u64 * mem::size_of::<TrieValueEntry>() as u64
+    }
+}
+
+/// Builds an on-disk HWDB trie (`hwdb.bin`) from `.hwdb` text sources.
+///
+/// Mirrors `systemd-hwdb update`: parses match-line blocks followed by space-indented
+/// `KEY=value` property lines, compresses the matches into a PATRICIA trie, and serializes the
+/// result into the exact format [`UdevHwdb::new`](crate::UdevHwdb::new) reads back.
+pub struct TrieWriter {
+    root: BuildNode,
+    /// Number of sources merged so far; doubles as each source's `file_priority`, so later
+    /// `add_source` calls win conflicts over earlier ones (as later files do under
+    /// `systemd-hwdb update`).
+    source_count: u16,
+}
+
+impl TrieWriter {
+    /// Creates an empty [TrieWriter].
+    pub fn new() -> Self {
+        Self {
+            root: BuildNode::new(Vec::new()),
+            source_count: 0,
+        }
+    }
+
+    /// Parses one `.hwdb` text source and merges its match blocks into the trie being built.
+    ///
+    /// A block is a run of one or more match lines followed by one or more space-indented
+    /// `KEY=value` property lines; a blank line or `#`-comment ends the current block. Sources
+    /// added later take priority over earlier ones when they define the same key (see
+    /// [`TrieValueEntry::file_priority`]).
+    pub fn add_source(&mut self, filename: &str, text: &str) -> Result<()> {
+        let file_priority = self.source_count;
+        self.source_count = self.source_count.saturating_add(1);
+
+        let mut matches: Vec<String> = Vec::new();
+        let mut properties: Vec<(String, String, u32)> = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                self.flush_block(filename, file_priority, &mut matches, &mut properties);
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix(' ') {
+                let rest = rest.trim_start();
+                let (key, value) = rest
+                    .split_once('=')
+                    .ok_or_else(|| Error::UdevHwdb(format!("expected KEY=value property line, got {line:?}")))?;
+                // Keys are stored with one leading space, the prefix the reader requires of a
+                // property key.
+                properties.push((format!(" {key}"), value.to_owned(), line_number as u32 + 1));
+            } else {
+                if !properties.is_empty() {
+                    // A new match line after properties starts the next block.
+                    self.flush_block(filename, file_priority, &mut matches, &mut properties);
+                }
+                matches.push(line.to_owned());
+            }
+        }
+
+        self.flush_block(filename, file_priority, &mut matches, &mut properties);
+
+        Ok(())
+    }
+
+    fn flush_block(
+        &mut self,
+        filename: &str,
+        file_priority: u16,
+        matches: &mut Vec<String>,
+        properties: &mut Vec<(String, String, u32)>,
+    ) {
+        for m in matches.drain(..) {
+            for (k, v, line_number) in properties.iter() {
+                self.root.insert(
+                    m.as_bytes(),
+                    BuildValue {
+                        key: k.clone(),
+                        value: v.clone(),
+                        filename: filename.to_owned(),
+                        line_number: *line_number,
+                        file_priority,
+                    },
+                );
+            }
+        }
+        properties.clear();
+    }
+
+    fn flatten(node: &BuildNode, strings: &mut StringPool, flat: &mut Vec<FlatNode>) -> usize {
+        let mut children = Vec::with_capacity(node.children.len());
+        for (&c, child) in &node.children {
+            let idx = Self::flatten(child, strings, flat);
+            children.push((c, idx));
+        }
+
+        let values = node
+            .values
+            .iter()
+            .map(|v| FlatValue {
+                key_off: strings.intern(&v.key),
+                value_off: strings.intern(&v.value),
+                filename_off: strings.intern(&v.filename),
+                line_number: v.line_number,
+                file_priority: v.file_priority,
+            })
+            .collect();
+
+        let prefix_off = if node.prefix.is_empty() {
+            0
+        } else {
+            strings.intern(&String::from_utf8_lossy(&node.prefix))
+        };
+
+        flat.push(FlatNode {
+            prefix_off,
+            children,
+            values,
+            byte_offset: 0,
+        });
+        flat.len() - 1
+    }
+
+    /// Serializes the accumulated trie into the on-disk `hwdb.bin` byte layout.
+    pub fn compile(self) -> Vec<u8> {
+        let mut strings = StringPool::new();
+        let mut flat = Vec::new();
+        let root_idx = Self::flatten(&self.root, &mut strings, &mut flat);
+
+        let mut offset = 0u64;
+        for n in &mut flat {
+            n.byte_offset = offset;
+            offset += n.byte_size();
+        }
+
+        let header_size = mem::size_of::<TrieHeader>() as u64;
+        let strings_off = header_size;
+        let strings_len = strings.buf.len() as u64;
+        let node_base = strings_off + strings_len;
+
+        let mut nodes_buf = Vec::with_capacity(offset as usize);
+        for n in &flat {
+            let children: Vec<TrieChildEntry> = n
+                .children
+                .iter()
+                .map(|&(c, idx)| TrieChildEntry::new().with_c(c).with_child_off(node_base + flat[idx].byte_offset))
+                .collect();
+
+            let trie_node = TrieNode::new()
+                .with_prefix_off(if n.prefix_off == 0 { 0 } else { strings_off + n.prefix_off })
+                .with_children_count(children.len() as u64)
+                .with_values_count(n.values.len() as u64);
+
+            nodes_buf.extend_from_slice(&trie_node.to_bytes());
+            for c in &children {
+                nodes_buf.extend_from_slice(&c.to_bytes());
+            }
+            for v in &n.values {
+                let rebase = |off: u64| if off == 0 { 0 } else { strings_off + off };
+                let entry = TrieValueEntry::new()
+                    .with_key_off(rebase(v.key_off))
+                    .with_value_off(rebase(v.value_off))
+                    .with_filename_off(rebase(v.filename_off))
+                    .with_line_number(v.line_number)
+                    .with_file_priority(v.file_priority);
+                nodes_buf.extend_from_slice(&entry.to_bytes());
+            }
+        }
+
+        let header = TrieHeader::build(
+            mem::size_of::<TrieNode>() as u64,
+            mem::size_of::<TrieChildEntry>() as u64,
+            mem::size_of::<TrieValueEntry>() as u64,
+            strings_off,
+            strings_len,
+            node_base + flat[root_idx].byte_offset,
+            nodes_buf.len() as u64,
+        );
+
+        let mut out = header.to_bytes();
+        out.extend_from_slice(&strings.buf);
+        out.extend_from_slice(&nodes_buf);
+        out
+    }
+}
+
+impl Default for TrieWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,7 @@
+use std::io::Write;
 use std::sync::Arc;
 
-use udevrs::{Result, Udev, UdevHwdb};
+use udevrs::{Result, Udev, UdevDevice, UdevHwdb};
 
 mod common;
 
@@ -23,3 +24,365 @@ fn parse_hwdb() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn compile_and_round_trip_hwdb() -> Result<()> {
+    common::init();
+
+    let source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Linux Foundation\n";
+    let compiled = UdevHwdb::compile([("20-test.hwdb", source)])?;
+
+    let entries: Vec<_> = udevrs::TrieHeader::try_from(compiled.as_slice())
+        .map(|head| UdevHwdb::parse_nodes(&head, &compiled))
+        .into_iter()
+        .flatten()
+        .collect();
+
+    assert!(!entries.is_empty());
+
+    let mut tmp = tempfile::NamedTempFile::new().expect("unable to create temp hwdb.bin");
+    tmp.write_all(&compiled).expect("unable to write compiled hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", tmp.path());
+    let udev = Arc::new(Udev::new());
+    let mut hwdb = UdevHwdb::new(udev)?;
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned());
+
+    assert_eq!(found.as_deref(), Some("Linux Foundation"));
+
+    Ok(())
+}
+
+#[test]
+fn priority_conflict_resolves_for_exact_match() -> Result<()> {
+    common::init();
+
+    // Same key, same (non-glob) match pattern, defined by two sources; the later source should
+    // win and the loser must never reach the property list (not even as a duplicate entry ahead
+    // of the winner).
+    let low = "usb:v1D6Bp0001\n ID_VENDOR_FROM_DATABASE=Low Priority\n";
+    let high = "usb:v1D6Bp0001\n ID_VENDOR_FROM_DATABASE=High Priority\n";
+    let compiled = UdevHwdb::compile([("10-low.hwdb", low), ("20-high.hwdb", high)])?;
+
+    let mut tmp = tempfile::NamedTempFile::new().expect("unable to create temp hwdb.bin");
+    tmp.write_all(&compiled).expect("unable to write compiled hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", tmp.path());
+    let udev = Arc::new(Udev::new());
+    let mut hwdb = UdevHwdb::new(udev)?;
+
+    let matches: Vec<_> = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .filter(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned())
+        .collect();
+
+    assert_eq!(matches, vec!["High Priority".to_owned()]);
+
+    Ok(())
+}
+
+#[test]
+fn compile_reports_accurate_file_size() -> Result<()> {
+    common::init();
+
+    // A tree with at least one child puts the root (pushed last by `flatten`'s post-order
+    // traversal) near the *end* of the nodes region rather than the start, which is what
+    // exposes `file_size` being computed from the root's own offset instead of the nodes
+    // region's start.
+    let source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Linux Foundation\n";
+    let compiled = UdevHwdb::compile([("20-test.hwdb", source)])?;
+
+    let head = udevrs::TrieHeader::try_from(compiled.as_slice())?;
+
+    assert_eq!(head.file_size(), compiled.len() as u64);
+
+    Ok(())
+}
+
+#[test]
+fn compile_compresses_shared_prefixes() -> Result<()> {
+    common::init();
+
+    // These two match lines share a 13-byte prefix and diverge only in their last two
+    // characters; a PATRICIA trie should represent that shared run with a handful of nodes
+    // rather than one node per byte.
+    let source = "usb:v1D6Bp0001*\n ID_MATCH=one\nusb:v1D6Bp0002*\n ID_MATCH=two\n";
+    let compiled = UdevHwdb::compile([("20-test.hwdb", source)])?;
+
+    let node_count = udevrs::TrieHeader::try_from(compiled.as_slice())
+        .map(|head| UdevHwdb::parse_nodes(&head, &compiled).count())
+        .unwrap_or(0);
+
+    let key_bytes = "usb:v1D6Bp0001*".len();
+    assert!(
+        node_count < key_bytes,
+        "expected prefix compression to produce fewer nodes than key bytes, got {node_count} nodes for a {key_bytes}-byte key"
+    );
+
+    Ok(())
+}
+
+fn open_compiled(source: &str) -> Result<UdevHwdb> {
+    let compiled = UdevHwdb::compile([("20-test.hwdb", source)])?;
+
+    let mut tmp = tempfile::NamedTempFile::new().expect("unable to create temp hwdb.bin");
+    tmp.write_all(&compiled).expect("unable to write compiled hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", tmp.path());
+    // Leak the handle so the temp file outlives the test body; it's cleaned up with the OS temp dir.
+    std::mem::forget(tmp);
+
+    let udev = Arc::new(Udev::new());
+    UdevHwdb::new(udev)
+}
+
+#[test]
+fn fnmatch_bracket_range() -> Result<()> {
+    common::init();
+
+    let source = "usb:v1D6Bp000[1-3]\n ID_MATCH=range\n";
+    let mut hwdb = open_compiled(source)?;
+
+    for modalias in ["usb:v1D6Bp0001", "usb:v1D6Bp0002", "usb:v1D6Bp0003"] {
+        let found = hwdb
+            .get_properties_list_entry(modalias, 0)?
+            .find(|e| e.name() == "ID_MATCH")
+            .map(|e| e.value().to_owned());
+        assert_eq!(found.as_deref(), Some("range"), "expected {modalias} to match");
+    }
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0004", 0)?
+        .find(|e| e.name() == "ID_MATCH");
+    assert!(found.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn fnmatch_bracket_negation() -> Result<()> {
+    common::init();
+
+    let source = "usb:v1D6Bp00[!9]1\n ID_MATCH=not-nine\n";
+    let mut hwdb = open_compiled(source)?;
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_MATCH");
+    assert!(found.is_some());
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0091", 0)?
+        .find(|e| e.name() == "ID_MATCH");
+    assert!(found.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn fnmatch_multiple_wildcards() -> Result<()> {
+    common::init();
+
+    let source = "usb:v*p0001*\n ID_MATCH=multi-star\n";
+    let mut hwdb = open_compiled(source)?;
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001rev02", 0)?
+        .find(|e| e.name() == "ID_MATCH")
+        .map(|e| e.value().to_owned());
+    assert_eq!(found.as_deref(), Some("multi-star"));
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0002rev02", 0)?
+        .find(|e| e.name() == "ID_MATCH");
+    assert!(found.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn reload_picks_up_changes_to_the_backing_file() -> Result<()> {
+    common::init();
+
+    let old_source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Old Vendor\n";
+    let old_compiled = UdevHwdb::compile([("20-test.hwdb", old_source)])?;
+
+    let mut tmp = tempfile::NamedTempFile::new().expect("unable to create temp hwdb.bin");
+    tmp.write_all(&old_compiled).expect("unable to write compiled hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", tmp.path());
+    let udev = Arc::new(Udev::new());
+    let mut hwdb = UdevHwdb::new(udev)?;
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned());
+    assert_eq!(found.as_deref(), Some("Old Vendor"));
+
+    // Replace the backing file's contents in place, the way `systemd-hwdb update` would, then
+    // `reload` and confirm the new contents are seen.
+    let new_source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=New Vendor\n";
+    let new_compiled = UdevHwdb::compile([("20-test.hwdb", new_source)])?;
+    std::fs::write(tmp.path(), &new_compiled).expect("unable to overwrite compiled hwdb");
+
+    hwdb.reload()?;
+
+    let found = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned());
+    assert_eq!(found.as_deref(), Some("New Vendor"));
+
+    Ok(())
+}
+
+#[test]
+fn query_device_applies_matching_properties() -> Result<()> {
+    common::init();
+
+    let source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Linux Foundation\n";
+    let mut hwdb = open_compiled(source)?;
+
+    let udev = Arc::new(Udev::new());
+    let mut device = UdevDevice::new(udev)?;
+    device.set_property("MODALIAS", "usb:v1D6Bp0001");
+
+    hwdb.query_device(&mut device)?;
+
+    assert_eq!(device.property_value("ID_VENDOR_FROM_DATABASE"), Some("Linux Foundation"));
+
+    Ok(())
+}
+
+/// Hand-assembles a minimal hwdb format v1 (16-byte value entry) `hwdb.bin` byte-for-byte: a
+/// single root node holding one value, which an empty search matches. The crate's own node/header
+/// serializers (`TrieHeader::build`, `TrieNode`'s `with_*`/`to_bytes`, ...) are `pub(crate)` and
+/// unavailable here, so this mirrors their on-disk layout directly instead.
+fn build_v1_hwdb(key: &str, value: &str) -> Vec<u8> {
+    const HEADER_SIZE: u64 = 88;
+    const NODE_SIZE: u64 = 24;
+    const CHILD_ENTRY_SIZE: u64 = 9;
+    const VALUE_ENTRY_SIZE: u64 = 16;
+
+    let mut strings = vec![0u8];
+    let key_off = strings.len() as u64;
+    strings.extend_from_slice(key.as_bytes());
+    strings.push(0);
+    let value_off = strings.len() as u64;
+    strings.extend_from_slice(value.as_bytes());
+    strings.push(0);
+
+    let strings_off = HEADER_SIZE;
+    let strings_len = strings.len() as u64;
+    let node_base = strings_off + strings_len;
+
+    let mut node = Vec::new();
+    node.extend_from_slice(&0u64.to_le_bytes()); // prefix_off: no shared prefix
+    node.extend_from_slice(&0u64.to_le_bytes()); // children_count
+    node.extend_from_slice(&1u64.to_le_bytes()); // values_count
+    node.extend_from_slice(&(strings_off + key_off).to_le_bytes()); // key_off
+    node.extend_from_slice(&(strings_off + value_off).to_le_bytes()); // value_off
+
+    let nodes_len = NODE_SIZE + VALUE_ENTRY_SIZE;
+    let file_size = node_base + nodes_len;
+
+    let mut buf = Vec::with_capacity(file_size as usize);
+    buf.extend_from_slice(b"KSLPHHRH");
+    buf.extend_from_slice(&1u64.to_le_bytes()); // tool_version
+    buf.extend_from_slice(&file_size.to_le_bytes());
+    buf.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    buf.extend_from_slice(&NODE_SIZE.to_le_bytes());
+    buf.extend_from_slice(&CHILD_ENTRY_SIZE.to_le_bytes());
+    buf.extend_from_slice(&VALUE_ENTRY_SIZE.to_le_bytes());
+    buf.extend_from_slice(&strings_off.to_le_bytes());
+    buf.extend_from_slice(&strings_len.to_le_bytes());
+    buf.extend_from_slice(&node_base.to_le_bytes()); // nodes_root_off
+    buf.extend_from_slice(&nodes_len.to_le_bytes());
+
+    buf.extend_from_slice(&strings);
+    buf.extend_from_slice(&node);
+
+    buf
+}
+
+#[test]
+fn differently_sized_databases_parse_independently() -> Result<()> {
+    common::init();
+
+    // A hand-built v1 (16-byte value entry) database and this crate's own v2 (32-byte) output,
+    // open at the same time: each `UdevHwdb` carries its own `TrieLayout` derived from its own
+    // header, so neither's record sizes should leak into the other's parsing.
+    let v1_compiled = build_v1_hwdb(" ID_MATCH", "v1-match");
+    let mut v1_tmp = tempfile::NamedTempFile::new().expect("unable to create temp v1 hwdb.bin");
+    v1_tmp.write_all(&v1_compiled).expect("unable to write v1 hwdb");
+
+    let v2_source = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Linux Foundation\n";
+    let v2_compiled = UdevHwdb::compile([("20-test.hwdb", v2_source)])?;
+    let mut v2_tmp = tempfile::NamedTempFile::new().expect("unable to create temp v2 hwdb.bin");
+    v2_tmp.write_all(&v2_compiled).expect("unable to write v2 hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", v1_tmp.path());
+    let udev_v1 = Arc::new(Udev::new());
+    let mut hwdb_v1 = UdevHwdb::new(udev_v1)?;
+
+    std::env::set_var("UDEV_HWDB_BIN", v2_tmp.path());
+    let udev_v2 = Arc::new(Udev::new());
+    let mut hwdb_v2 = UdevHwdb::new(udev_v2)?;
+
+    let v2_found = hwdb_v2
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned());
+    assert_eq!(v2_found.as_deref(), Some("Linux Foundation"));
+
+    let v1_found = hwdb_v1
+        .get_properties_list_entry("", 0)?
+        .find(|e| e.name() == "ID_MATCH")
+        .map(|e| e.value().to_owned());
+    assert_eq!(v1_found.as_deref(), Some("v1-match"));
+
+    // Re-check the v2 database after having parsed the v1 one; a regression that reintroduced
+    // any shared/global record-size assumption would desync this lookup.
+    let v2_found_again = hwdb_v2
+        .get_properties_list_entry("usb:v1D6Bp0001", 0)?
+        .find(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned());
+    assert_eq!(v2_found_again.as_deref(), Some("Linux Foundation"));
+
+    Ok(())
+}
+
+#[test]
+fn priority_conflict_resolves_through_glob_match() -> Result<()> {
+    common::init();
+
+    // Real hwdb match patterns are almost always `*`-terminated, which routes lookups through
+    // `LineBuf::walk` instead of `trie_search`'s direct descent; the same priority-based
+    // conflict resolution must apply there too.
+    let low = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=Low Priority\n";
+    let high = "usb:v1D6Bp0001*\n ID_VENDOR_FROM_DATABASE=High Priority\n";
+    let compiled = UdevHwdb::compile([("10-low.hwdb", low), ("20-high.hwdb", high)])?;
+
+    let mut tmp = tempfile::NamedTempFile::new().expect("unable to create temp hwdb.bin");
+    tmp.write_all(&compiled).expect("unable to write compiled hwdb");
+
+    std::env::set_var("UDEV_HWDB_BIN", tmp.path());
+    let udev = Arc::new(Udev::new());
+    let mut hwdb = UdevHwdb::new(udev)?;
+
+    let matches: Vec<_> = hwdb
+        .get_properties_list_entry("usb:v1D6Bp0001rev02", 0)?
+        .filter(|e| e.name() == "ID_VENDOR_FROM_DATABASE")
+        .map(|e| e.value().to_owned())
+        .collect();
+
+    assert_eq!(matches, vec!["High Priority".to_owned()]);
+
+    Ok(())
+}